@@ -1,10 +1,29 @@
-use std::{collections::VecDeque, num::NonZeroUsize, ops::RangeBounds, slice::SliceIndex};
+use std::{
+    cmp::Ordering,
+    collections::{TryReserveError, VecDeque},
+    num::NonZeroUsize,
+    ops::RangeBounds,
+    slice::SliceIndex,
+};
 
 /// A buffered iterator (or a lazy stack) whose elements are generated from an iterator, and stored in an internal buffer
+///
+/// The buffer is treated as the middle section of a lazy deque: items drawn from the front of the
+/// source live at the front of `buf`, items drawn from the back live at the back of `buf`, and the
+/// raw `iter` supplies the still-ungenerated middle. `back` records how many of the trailing items
+/// in `buf` were produced by `iter.next_back()`.
 #[derive(Clone, Debug)]
 pub struct BufIter<Iter: Iterator> {
     iter: Iter,
     buf: VecDeque<Iter::Item>,
+    back: usize,
+    /// Optional lookahead limit: when set, the front section is kept to at most this many items by
+    /// discarding the oldest unconsumed item from the front, like a fixed-size ring buffer.
+    cap: Option<NonZeroUsize>,
+    /// How many front items have been discarded by ring-buffer overflow. Indices returned by
+    /// `peek`/`search` are relative to the current (post-discard) head; items consumed with `pop`
+    /// are not counted here.
+    head: usize,
 }
 
 // Public implementation
@@ -16,30 +35,232 @@ where
         BufIter {
             iter: iter.into_iter(),
             buf: VecDeque::new(),
+            back: 0,
+            cap: None,
+            head: 0,
         }
     }
-    /// Pushes an item to the front of the iterator
-    pub fn push(&mut self, item: Iter::Item) {
+    /// Creates a `BufIter` whose front lookahead is bounded to `cap` items.
+    ///
+    /// Pre-filling past `cap` discards the oldest unconsumed item from the front, giving
+    /// fixed-memory sliding-window semantics over an unbounded or infinite source. `peek(n)` for
+    /// `n >= cap` returns `None` without allocating.
+    pub fn with_capacity<I: IntoIterator>(iter: I, cap: NonZeroUsize) -> BufIter<I::IntoIter> {
+        BufIter {
+            iter: iter.into_iter(),
+            buf: VecDeque::with_capacity(cap.get()),
+            back: 0,
+            cap: Some(cap),
+            head: 0,
+        }
+    }
+    /// Sets the lookahead limit, bounding how far `peek`/`peek_slice`/`prepare_*` pre-fill.
+    pub fn set_lookahead_limit(&mut self, cap: NonZeroUsize) {
+        self.cap = Some(cap);
+    }
+    /// Pushes an item to the front of the iterator.
+    ///
+    /// When a lookahead limit is set and the buffer is already full, the item is rejected and
+    /// returned as `Some(item)`; otherwise it is buffered and `None` is returned.
+    pub fn push(&mut self, item: Iter::Item) -> Option<Iter::Item> {
+        if let Some(cap) = self.cap {
+            if self.front_len() >= cap.get() {
+                return Some(item);
+            }
+        }
         self.buf.push_front(item);
+        None
     }
     /// Returns the next item in the iterator.
     pub fn pop(&mut self) -> Option<Iter::Item> {
-        if self.buf.is_empty() {
-            self.iter.next()
-        } else {
+        if self.front_len() > 0 {
             self.buf.pop_front()
+        } else if let Some(item) = self.iter.next() {
+            Some(item)
+        } else {
+            // Nothing left in the middle; the back-drawn items are now the head.
+            let item = self.buf.pop_front();
+            if item.is_some() {
+                self.back -= 1;
+            }
+            item
+        }
+    }
+    /// Returns the last item in the iterator, pulling from `iter.next_back()` when needed.
+    pub fn pop_back(&mut self) -> Option<Iter::Item>
+    where
+        Iter: DoubleEndedIterator,
+    {
+        if self.back > 0 {
+            self.back -= 1;
+            self.buf.pop_back()
+        } else if let Some(item) = self.iter.next_back() {
+            Some(item)
+        } else {
+            // Nothing left in the middle; the front-drawn items are now the tail.
+            self.buf.pop_back()
+        }
+    }
+    /// Returns a reference to the `n`th item from the back of the iterator, without consuming.
+    pub fn peek_back(&mut self, n: usize) -> Option<&Iter::Item>
+    where
+        Iter: DoubleEndedIterator,
+    {
+        // Once `next_back` is exhausted the source is fully drained, so the trailing front-drawn
+        // items are themselves the tail; fall back to the whole buffer to agree with `pop_back`.
+        if self.exceeds_cap(n) {
+            return None;
         }
+        let _ = self.prepare_back_n(n + 1);
+        self.buf.get(self.buf.len().checked_sub(n + 1)?)
+    }
+    /// Returns a mutable reference to the `n`th item from the back of the iterator, without consuming.
+    pub fn peek_back_mut(&mut self, n: usize) -> Option<&mut Iter::Item>
+    where
+        Iter: DoubleEndedIterator,
+    {
+        if self.exceeds_cap(n) {
+            return None;
+        }
+        let _ = self.prepare_back_n(n + 1);
+        let idx = self.buf.len().checked_sub(n + 1)?;
+        self.buf.get_mut(idx)
     }
     /// Returns a reference to the next item in the iterator, without consuming.
     pub fn peek(&mut self, n: usize) -> Option<&Iter::Item> {
+        if self.exceeds_cap(n) {
+            return None;
+        }
         self.prepare_n(n + 1).ok()?;
         self.buf.get(n)
     }
     /// Returns a mutable reference to the next item in the iterator, without consuming.
     pub fn peek_mut(&mut self, n: usize) -> Option<&mut Iter::Item> {
+        if self.exceeds_cap(n) {
+            return None;
+        }
         self.prepare_n(n + 1).ok()?;
         self.buf.get_mut(n)
     }
+    /// Consumes and returns the next item only if `f` returns `true` for it.
+    ///
+    /// If `f` returns `false` or the iterator is exhausted, nothing is consumed; a peeked item
+    /// stays in the buffer.
+    pub fn next_if<F>(&mut self, f: F) -> Option<Iter::Item>
+    where
+        F: FnOnce(&Iter::Item) -> bool,
+    {
+        match self.peek(0) {
+            Some(item) if f(item) => self.pop(),
+            _ => None,
+        }
+    }
+    /// Consumes and returns the next item only if it equals `expected`.
+    pub fn next_if_eq<T>(&mut self, expected: &T) -> Option<Iter::Item>
+    where
+        T: ?Sized,
+        Iter::Item: PartialEq<T>,
+    {
+        self.next_if(|item| item == expected)
+    }
+    /// Returns a borrowing adapter that yields items while `f` holds, leaving the first
+    /// non-matching item in the buffer (unlike [`Iterator::take_while`], which discards it).
+    pub fn take_while_peek<F>(&mut self, f: F) -> TakeWhilePeek<'_, Iter, F>
+    where
+        F: FnMut(&Iter::Item) -> bool,
+    {
+        TakeWhilePeek { iter: self, pred: f }
+    }
+    /// Like [`BufIter::peek`], but routes preallocation through [`VecDeque::try_reserve`] and
+    /// propagates a [`TryReserveError`] instead of aborting on allocation failure.
+    pub fn try_peek(&mut self, n: usize) -> Result<Option<&Iter::Item>, TryReserveError> {
+        if self.exceeds_cap(n) {
+            return Ok(None);
+        }
+        self.try_prepare_n(n + 1)?;
+        Ok(self.buf.get(n))
+    }
+    /// Fallibly pre-fills the buffer so that at least `n` front items are available, using
+    /// [`VecDeque::try_reserve`] so a failed allocation is reported rather than aborting.
+    pub fn try_prepare_n(&mut self, n: usize) -> Result<(), TryReserveError> {
+        let deficit = n.saturating_sub(self.front_len());
+        self.buf.try_reserve(deficit)?;
+        for _ in 0..deficit {
+            let Some(item) = self.iter.next() else {
+                break;
+            };
+            self.buffer_front(item);
+        }
+        Ok(())
+    }
+    /// Searches the monotonically ordered pending items for one matching `f`, using exponential
+    /// (galloping) search, which makes only `O(log k)` probes and buffers `O(k)` items, where `k`
+    /// is the match position.
+    ///
+    /// `f` should compare a buffered item against the target, returning [`Ordering::Less`] when the
+    /// item sorts before it. Returns `Ok(index)` of a match, or `Err(insertion_point)` otherwise,
+    /// exactly like [`slice::binary_search_by`]; indices are relative to the current head. Under a
+    /// lookahead limit only the items within the limit are searched.
+    pub fn search_by<F>(&mut self, mut f: F) -> Result<usize, usize>
+    where
+        F: FnMut(&Iter::Item) -> Ordering,
+    {
+        let mut bound = 1;
+        loop {
+            match self.peek(bound) {
+                Some(item) if f(item) == Ordering::Less => bound *= 2,
+                // Either we reached an element `>=` the target, or the source was exhausted, or
+                // the probe reached the lookahead limit.
+                _ => break,
+            }
+        }
+        let lo = bound / 2;
+        let hi = (bound + 1).min(self.front_len());
+        let window = &self.buf.make_contiguous()[lo..hi];
+        match window.binary_search_by(f) {
+            Ok(i) => Ok(lo + i),
+            Err(i) => Err(lo + i),
+        }
+    }
+    /// Searches the monotonically ordered pending items for `x`. See [`BufIter::search_by`].
+    pub fn search(&mut self, x: &Iter::Item) -> Result<usize, usize>
+    where
+        Iter::Item: Ord,
+    {
+        self.search_by(|item| item.cmp(x))
+    }
+    /// Searches the pending items ordered by a key extracted with `f`. See [`BufIter::search_by`].
+    pub fn search_by_key<B, F>(&mut self, b: &B, mut f: F) -> Result<usize, usize>
+    where
+        F: FnMut(&Iter::Item) -> B,
+        B: Ord,
+    {
+        self.search_by(|item| f(item).cmp(b))
+    }
+    /// Returns a non-consuming iterator over overlapping windows of `size` pending items.
+    ///
+    /// Like [`slice::windows`], but lazily fills the buffer one item at a time. When the source is
+    /// exhausted and fewer than `size` items remain, iteration stops without yielding a short
+    /// window. Nothing is consumed, so a caller may inspect the windows and then decide how many
+    /// items to [`pop`](BufIter::pop).
+    pub fn peek_windows(&mut self, size: usize) -> PeekWindows<'_, Iter> {
+        PeekWindows {
+            iter: self,
+            size,
+            pos: 0,
+        }
+    }
+    /// Returns a non-consuming iterator over non-overlapping chunks of `size` pending items.
+    ///
+    /// Like [`slice::chunks`], but lazily fills the buffer. When the source is exhausted the final
+    /// chunk may be shorter than `size`. Nothing is consumed.
+    pub fn peek_chunks(&mut self, size: usize) -> PeekChunks<'_, Iter> {
+        PeekChunks {
+            iter: self,
+            size,
+            pos: 0,
+        }
+    }
     /// Returns a reference to a slice of items in the iterator corresponding to the provided range.
     pub fn peek_slice<R>(&mut self, index: R) -> Option<&R::Output>
     where
@@ -69,6 +290,123 @@ where
     }
 }
 
+/// A borrowing adapter returned by [`BufIter::take_while_peek`].
+///
+/// Yields items from the underlying [`BufIter`] while the predicate holds, stopping at — but not
+/// consuming — the first item for which it returns `false`.
+pub struct TakeWhilePeek<'a, Iter: Iterator, F> {
+    iter: &'a mut BufIter<Iter>,
+    pred: F,
+}
+
+impl<Iter: Iterator, F> std::fmt::Debug for TakeWhilePeek<'_, Iter, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TakeWhilePeek").finish_non_exhaustive()
+    }
+}
+
+impl<Iter, F> Iterator for TakeWhilePeek<'_, Iter, F>
+where
+    Iter: Iterator,
+    F: FnMut(&Iter::Item) -> bool,
+{
+    type Item = Iter::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.peek(0)?;
+        if (self.pred)(item) {
+            self.iter.pop()
+        } else {
+            None
+        }
+    }
+}
+
+/// A non-consuming lending iterator over overlapping windows, returned by
+/// [`BufIter::peek_windows`].
+///
+/// Because each window borrows from the backing buffer — which may reallocate as more items are
+/// pulled in — the yielded slices cannot outlive the call that produced them, so this is a lending
+/// iterator with an inherent `next` rather than an [`Iterator`] impl.
+pub struct PeekWindows<'a, Iter: Iterator> {
+    iter: &'a mut BufIter<Iter>,
+    size: usize,
+    pos: usize,
+}
+
+impl<Iter: Iterator> std::fmt::Debug for PeekWindows<'_, Iter> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeekWindows")
+            .field("size", &self.size)
+            .field("pos", &self.pos)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Iter: Iterator> PeekWindows<'_, Iter> {
+    /// Returns the next window, or `None` once fewer than `size` items remain.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&[Iter::Item]> {
+        if self.size == 0 {
+            return None;
+        }
+        let start = self.pos;
+        let _ = self.iter.prepare_n(start + self.size);
+        if self.iter.front_len() < start + self.size {
+            return None;
+        }
+        self.pos += 1;
+        let slice = self.iter.buf.make_contiguous();
+        Some(&slice[start..start + self.size])
+    }
+}
+
+/// A non-consuming lending iterator over non-overlapping chunks, returned by
+/// [`BufIter::peek_chunks`]. See [`PeekWindows`] for why this is a lending iterator.
+pub struct PeekChunks<'a, Iter: Iterator> {
+    iter: &'a mut BufIter<Iter>,
+    size: usize,
+    pos: usize,
+}
+
+impl<Iter: Iterator> std::fmt::Debug for PeekChunks<'_, Iter> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeekChunks")
+            .field("size", &self.size)
+            .field("pos", &self.pos)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Iter: Iterator> PeekChunks<'_, Iter> {
+    /// Returns the next chunk; the final chunk may be shorter than `size`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&[Iter::Item]> {
+        if self.size == 0 {
+            return None;
+        }
+        let start = self.pos;
+        let _ = self.iter.prepare_n(start + self.size);
+        let avail = self.iter.front_len().saturating_sub(start);
+        if avail == 0 {
+            return None;
+        }
+        let take = avail.min(self.size);
+        self.pos += take;
+        let slice = self.iter.buf.make_contiguous();
+        Some(&slice[start..start + take])
+    }
+}
+
+impl<Iter> DoubleEndedIterator for BufIter<Iter>
+where
+    Iter: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.pop_back()
+    }
+}
+
 impl<Iter> ExactSizeIterator for BufIter<Iter> where Iter: ExactSizeIterator {}
 
 // Private implementation
@@ -76,48 +414,216 @@ impl<Iter> BufIter<Iter>
 where
     Iter: Iterator,
 {
+    /// Number of front-drawn items currently buffered (everything but the back section).
+    fn front_len(&self) -> usize {
+        self.buf.len() - self.back
+    }
+    /// Whether front index `n` lies at or beyond the lookahead limit.
+    fn exceeds_cap(&self, n: usize) -> bool {
+        matches!(self.cap, Some(cap) if n >= cap.get())
+    }
+    /// Buffers an item at the end of the front section, discarding the oldest unconsumed front
+    /// item first when the lookahead limit is reached so the front section never exceeds `cap`.
+    fn buffer_front(&mut self, item: Iter::Item) {
+        if let Some(cap) = self.cap {
+            if self.front_len() >= cap.get() {
+                // Ring-buffer overflow: drop the oldest unconsumed front item and count the
+                // discard in `head`; returned indices are then relative to the new head.
+                self.buf.pop_front();
+                self.head += 1;
+            }
+        }
+        self.buf.insert(self.front_len(), item);
+    }
     fn prepare<R>(&mut self, range: &R)
     where
         R: RangeBounds<usize>,
     {
-        let extra = match range.end_bound() {
-            std::ops::Bound::Included(ni) => (ni + 1).saturating_sub(self.buf.len()),
-            std::ops::Bound::Excluded(ne) => ne.saturating_sub(self.buf.len()),
+        let want = match range.end_bound() {
+            std::ops::Bound::Included(ni) => ni + 1,
+            std::ops::Bound::Excluded(ne) => *ne,
             std::ops::Bound::Unbounded => {
                 self.prepare_all();
                 return;
             }
         };
-        self.buf.reserve(extra);
-        for item in (&mut self.iter).take(extra) {
-            self.buf.push_back(item);
-        }
+        self.pull_front(want.saturating_sub(self.front_len()));
     }
     fn prepare_n(&mut self, n: usize) -> Result<(), NonZeroUsize> {
-        self.buf.reserve(n.saturating_sub(self.buf.len()));
-        while self.buf.len() < n {
+        self.pull_front(n.saturating_sub(self.front_len()));
+        match NonZeroUsize::new(n.saturating_sub(self.front_len())) {
+            Some(n) => Err(n),
+            None => Ok(()),
+        }
+    }
+    /// Pulls up to `deficit` items from the source onto the front section. Under a lookahead limit
+    /// this slides the window, discarding the oldest front items via [`buffer_front`].
+    fn pull_front(&mut self, deficit: usize) {
+        self.buf.reserve(deficit);
+        for _ in 0..deficit {
             let Some(item) = self.iter.next() else {
                 break;
             };
-            self.buf.push_back(item);
+            self.buffer_front(item);
         }
-        match NonZeroUsize::new(n.saturating_sub(self.buf.len())) {
+    }
+    fn prepare_back_n(&mut self, n: usize) -> Result<(), NonZeroUsize>
+    where
+        Iter: DoubleEndedIterator,
+    {
+        let target = match self.cap {
+            Some(cap) => n.min(cap.get()),
+            None => n,
+        };
+        self.buf.reserve(target.saturating_sub(self.back));
+        while self.back < target {
+            let Some(item) = self.iter.next_back() else {
+                break;
+            };
+            let idx = self.buf.len() - self.back;
+            self.buf.insert(idx, item);
+            self.back += 1;
+        }
+        match NonZeroUsize::new(n.saturating_sub(self.back)) {
             Some(n) => Err(n),
             None => Ok(()),
         }
     }
     fn prepare_all(&mut self) {
-        while let Some(item) = self.iter.next() {
-            self.buf.push_back(item);
+        match self.cap {
+            Some(cap) => {
+                while self.front_len() < cap.get() {
+                    let Some(item) = self.iter.next() else {
+                        break;
+                    };
+                    self.buffer_front(item);
+                }
+            }
+            None => {
+                while let Some(item) = self.iter.next() {
+                    self.buffer_front(item);
+                }
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn buf(items: &[i32]) -> BufIter<std::vec::IntoIter<i32>> {
+        BufIter::<std::vec::IntoIter<i32>>::new(items.to_vec())
+    }
+
+    #[test]
+    fn double_ended_pull_order() {
+        let mut it = buf(&[1, 2, 3, 4]);
+        assert_eq!(it.pop(), Some(1));
+        assert_eq!(it.pop_back(), Some(4));
+        assert_eq!(it.pop_back(), Some(3));
+        assert_eq!(it.pop(), Some(2));
+        assert_eq!(it.pop(), None);
+        assert_eq!(it.pop_back(), None);
+    }
+
+    #[test]
+    fn peek_back_agrees_with_pop_back_after_front_buffering() {
+        let mut it = buf(&[1, 2, 3]);
+        // Drain the source into the front buffer.
+        assert_eq!(it.peek(10), None);
+        assert_eq!(it.peek_back(0), Some(&3));
+        assert_eq!(it.peek_back(2), Some(&1));
+        assert_eq!(it.peek_back(3), None);
+        assert_eq!(it.pop_back(), Some(3));
+    }
+
+    #[test]
+    fn next_if_consumes_only_on_match() {
+        let mut it = buf(&[1, 2, 3]);
+        assert_eq!(it.next_if(|&x| x == 1), Some(1));
+        assert_eq!(it.next_if(|&x| x == 99), None);
+        assert_eq!(it.next_if_eq(&2), Some(2));
+        assert_eq!(it.peek(0), Some(&3));
+    }
+
+    #[test]
+    fn take_while_peek_leaves_non_match() {
+        let mut it = buf(&[1, 2, 3, 4]);
+        let taken: Vec<_> = it.take_while_peek(|&x| x < 3).collect();
+        assert_eq!(taken, vec![1, 2]);
+        // The first non-matching item is still there.
+        assert_eq!(it.pop(), Some(3));
+    }
+
+    #[test]
+    fn galloping_search_indices() {
+        let mut it = buf(&[1, 3, 5, 7, 9, 11]);
+        assert_eq!(it.search(&7), Ok(3));
+        assert_eq!(it.search(&6), Err(3));
+        assert_eq!(it.search(&0), Err(0));
+        assert_eq!(it.search(&100), Err(6));
+        // Indices are relative to the current head.
+        assert_eq!(it.pop(), Some(1));
+        assert_eq!(it.search(&7), Ok(2));
+    }
+
+    #[test]
+    fn bounded_lookahead_clamps() {
+        let cap = std::num::NonZeroUsize::new(2).unwrap();
+        let mut it = BufIter::<std::vec::IntoIter<i32>>::with_capacity(vec![1, 2, 3, 4], cap);
+        assert_eq!(it.peek(0), Some(&1));
+        assert_eq!(it.peek(1), Some(&2));
+        assert_eq!(it.peek(2), None);
+        // Consuming makes room to peek further.
+        assert_eq!(it.pop(), Some(1));
+        assert_eq!(it.peek(1), Some(&3)); // buffer now full again ([2, 3]).
+        // A push beyond the cap is rejected and returned.
+        assert_eq!(it.push(8), Some(8));
+        // Once there is room, push is accepted.
+        assert_eq!(it.pop(), Some(2));
+        assert_eq!(it.push(9), None);
+        assert_eq!(it.pop(), Some(9));
+    }
+
+    #[test]
+    fn bounded_lookahead_discards_oldest() {
+        let cap = std::num::NonZeroUsize::new(3).unwrap();
+        let mut it = BufIter::<std::vec::IntoIter<i32>>::with_capacity(vec![1, 2, 3, 4, 5, 6], cap);
+        // A pre-fill past the cap slides the window, discarding the oldest front items.
+        assert_eq!(it.peek_slice(0..5), None);
+        // The first two items were dropped; the window now starts at 3.
+        assert_eq!(it.peek(0), Some(&3));
+        assert_eq!(it.pop(), Some(3));
+        assert_eq!(it.pop(), Some(4));
+    }
+
+    #[test]
+    fn try_peek_succeeds() {
+        let mut it = buf(&[1, 2, 3]);
+        assert_eq!(it.try_peek(1), Ok(Some(&2)));
+        assert_eq!(it.try_peek(5), Ok(None));
+    }
+
+    #[test]
+    fn peek_windows_skips_short_tail() {
+        let mut it = buf(&[1, 2, 3, 4]);
+        let mut w = it.peek_windows(3);
+        assert_eq!(w.next(), Some(&[1, 2, 3][..]));
+        assert_eq!(w.next(), Some(&[2, 3, 4][..]));
+        assert_eq!(w.next(), None);
+        // Nothing was consumed.
+        assert_eq!(it.pop(), Some(1));
+    }
 
     #[test]
-    fn it_works() {
-        assert_eq!(4, 4);
+    fn peek_chunks_yields_short_final() {
+        let mut it = buf(&[1, 2, 3, 4, 5]);
+        let mut c = it.peek_chunks(2);
+        assert_eq!(c.next(), Some(&[1, 2][..]));
+        assert_eq!(c.next(), Some(&[3, 4][..]));
+        assert_eq!(c.next(), Some(&[5][..]));
+        assert_eq!(c.next(), None);
+        assert_eq!(it.pop(), Some(1));
     }
 }